@@ -19,10 +19,13 @@ use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Write;
 use std::io::Read;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
 
 use context::Context;
@@ -31,38 +34,125 @@ use rule::RuleIDOrCustom;
 use serde::{Deserialize, Serialize};
 use tree::{Tree, TreeLike};
 
+// Truncated BLAKE3 digest used to dedup chunks. 16 bytes keeps collisions
+// negligible while shrinking `seen_outputs` from O(total chunk bytes) to a
+// fixed-size key per unique chunk.
+type ChunkDigest = [u8; 16];
+
+fn chunk_digest(buffer: &[u8]) -> (blake3::Hash, ChunkDigest) {
+    let hash = blake3::hash(buffer);
+    let mut digest = [0u8; 16];
+    digest.copy_from_slice(&hash.as_bytes()[..16]);
+    (hash, digest)
+}
+
+fn chunk_file_name(hash: &blake3::Hash) -> String {
+    format!("chunk_{}", hash.to_hex())
+}
+
+// Folds any `chunk_<hex>` files under `work_dir/outputs/chunks` that aren't already indexed
+// into `seen_outputs`/`chunk_names`. Used both to build a fresh index from scratch (`new`,
+// called with empty collections) and, after `load`, to pick up chunks that were flushed to
+// disk after the last checkpoint but never made it into the serialized state.
+fn absorb_disk_chunks(work_dir: &str, seen_outputs: &mut HashSet<ChunkDigest>, chunk_names: &mut Vec<String>) {
+    let mut already_named: HashSet<String> = chunk_names.iter().cloned().collect();
+    if let Ok(entries) = std::fs::read_dir(format!("{}/outputs/chunks", work_dir)) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if already_named.contains(name) {
+                continue;
+            }
+            let hex = match name.strip_prefix("chunk_") {
+                Some(hex) => hex,
+                None => continue,
+            };
+            let hash = match blake3::Hash::from_hex(hex) {
+                Ok(hash) => hash,
+                Err(_) => continue,
+            };
+            let mut digest = [0u8; 16];
+            digest.copy_from_slice(&hash.as_bytes()[..16]);
+            if seen_outputs.insert(digest) {
+                chunk_names.push(name.to_string());
+                already_named.insert(name.to_string());
+            }
+        }
+    }
+}
+
+// Number of `add_tree` calls between automatic checkpoints.
+const CHECKPOINT_INTERVAL: usize = 1000;
+
 pub struct ChunkStoreWrapper {
     pub chunkstore: RwLock<ChunkStore>,
     pub is_locked: AtomicBool,
+    trees_since_checkpoint: AtomicUsize,
+    // Try-lock guarding the actual save(), so two threads crossing the checkpoint threshold at
+    // the same time don't both call `save()` and race two unsynchronized writers onto the same
+    // `chunkstore.state.tmp` path.
+    checkpoint_in_progress: AtomicBool,
 }
 impl ChunkStoreWrapper {
     #[must_use]
     pub fn new(work_dir: String) -> Self {
+        let chunkstore = ChunkStore::load(&work_dir).unwrap_or_else(|_| ChunkStore::new(work_dir));
         ChunkStoreWrapper {
-            chunkstore: RwLock::new(ChunkStore::new(work_dir)),
+            chunkstore: RwLock::new(chunkstore),
             is_locked: AtomicBool::new(false),
+            trees_since_checkpoint: AtomicUsize::new(0),
+            checkpoint_in_progress: AtomicBool::new(false),
+        }
+    }
+
+    // Persists the chunk store to disk, to be called periodically by the fuzzer's main loop
+    // (e.g. after every `add_tree`) so a kill between checkpoints loses at most the chunks
+    // produced since the last one.
+    pub fn checkpoint_if_due(&self) -> io::Result<()> {
+        if self.trees_since_checkpoint.fetch_add(1, Ordering::Relaxed) + 1 < CHECKPOINT_INTERVAL {
+            return Ok(());
+        }
+        if self
+            .checkpoint_in_progress
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Another thread is already checkpointing this interval; let it finish.
+            return Ok(());
         }
+        self.trees_since_checkpoint.store(0, Ordering::Relaxed);
+        let result = self.chunkstore.read().unwrap().save();
+        self.checkpoint_in_progress.store(false, Ordering::Release);
+        result
     }
 }
 
+const STATE_FILE_NAME: &str = "chunkstore.state";
+
 #[derive(Serialize, Deserialize)]
 pub struct ChunkStore {
     nts_to_chunks: HashMap<NTermID, Vec<(usize, NodeID)>>,
-    seen_outputs: HashSet<Vec<u8>>,
+    seen_outputs: HashSet<ChunkDigest>,
     trees: Vec<Tree>,
     work_dir: String,
-    number_of_chunks: usize,
+    chunk_names: Vec<String>,
 }
 
 impl ChunkStore {
     #[must_use]
     pub fn new(work_dir: String) -> Self {
+        let mut seen_outputs = HashSet::new();
+        let mut chunk_names = vec![];
+        absorb_disk_chunks(&work_dir, &mut seen_outputs, &mut chunk_names);
         ChunkStore {
             nts_to_chunks: HashMap::new(),
-            seen_outputs: HashSet::new(),
+            seen_outputs,
             trees: vec![],
             work_dir,
-            number_of_chunks: 0,
+            chunk_names,
         }
     }
 
@@ -77,19 +167,21 @@ impl ChunkStore {
             }
             let n = NodeID::from(i);
             tree.unparse(n, ctx, &mut buffer);
-            if !self.seen_outputs.contains(&buffer) {
-                self.seen_outputs.insert(buffer.clone());
+            let (hash, digest) = chunk_digest(&buffer);
+            if !self.seen_outputs.contains(&digest) {
+                self.seen_outputs.insert(digest);
                 self.nts_to_chunks
                     .entry(tree.get_rule(n, ctx).nonterm())
                     .or_insert_with(std::vec::Vec::new)
                     .push((id, n));
-                let mut file = File::create(format!(
-                    "{}/outputs/chunks/chunk_{:09}",
-                    self.work_dir, self.number_of_chunks
-                ))
-                .expect("RAND_596689790");
-                self.number_of_chunks += 1;
+                let name = chunk_file_name(&hash);
+                let mut file = File::create(format!("{}/outputs/chunks/{}", self.work_dir, name))
+                    .expect("RAND_596689790");
                 file.write_all(&buffer).expect("RAND_606896756");
+                // Identical chunks produced independently hash to the same file name, so
+                // re-deriving a chunk another run already wrote is a no-op overwrite rather
+                // than a second copy: storage is content-addressed.
+                self.chunk_names.push(name);
                 contains_new_chunk = true;
             }
         }
@@ -119,20 +211,55 @@ impl ChunkStore {
     
     pub fn get_chunk(&self)  -> Result<Vec<u8>,std::io::Error> {
         let mut buffer :Vec<u8> = Vec::new();
-        if self.number_of_chunks < 2 {
+        if self.chunk_names.len() < 2 {
             return Ok(buffer)
         }
         let mut rng = rand::thread_rng();
-        let high = self.number_of_chunks as usize;
-        let id = rng.gen_range(0..high);
+        let id = rng.gen_range(0..self.chunk_names.len());
         let path = format!(
-            "{}/outputs/chunks/chunk_{:09}",
-            self.work_dir,id
+            "{}/outputs/chunks/{}",
+            self.work_dir, self.chunk_names[id]
         );
         let mut file = File::open(path)?;
         file.read_to_end(&mut buffer)?;
         Ok(buffer)
     }
+
+    // Serializes the whole store (index + tree corpus, not the already-on-disk chunk bytes)
+    // to `work_dir/chunkstore.state`. Written to a temp file and renamed into place so that a
+    // kill mid-write leaves the previous, still-valid state file untouched.
+    pub fn save(&self) -> io::Result<()> {
+        let serialized =
+            serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let final_path = format!("{}/{}", self.work_dir, STATE_FILE_NAME);
+        let tmp_path = format!("{}.tmp", final_path);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serialized)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &final_path)?;
+        Ok(())
+    }
+
+    // Reloads a store previously written by `save`, so a restart can resume a multi-hour
+    // campaign's splice corpus instead of re-deriving every chunk from scratch. The caller's
+    // `work_dir` always wins over whatever was serialized, so a `chunkstore.state` copied to a
+    // new directory (or a different machine) reads and writes chunks there instead of the path
+    // that was in effect when `save()` ran.
+    pub fn load(work_dir: &str) -> io::Result<Self> {
+        let path = format!("{}/{}", work_dir, STATE_FILE_NAME);
+        let mut file = File::open(path)?;
+        let mut serialized = vec![];
+        file.read_to_end(&mut serialized)?;
+        let mut store: ChunkStore =
+            serde_json::from_slice(&serialized).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        store.work_dir = work_dir.to_string();
+        // The checkpoint that produced this state file may predate the crash by up to
+        // `CHECKPOINT_INTERVAL` `add_tree` calls; absorb whatever chunk files already exist on
+        // disk that the serialized index doesn't know about yet, so they aren't silently
+        // re-derived as "new" (and their index entries duplicated) on the next `add_tree`.
+        absorb_disk_chunks(&store.work_dir, &mut store.seen_outputs, &mut store.chunk_names);
+        Ok(store)
+    }
 }
 
 #[cfg(test)]
@@ -173,4 +300,156 @@ mod tests {
             "b c".as_bytes()
         );
     }
+
+    #[test]
+    fn save_load_round_trip() {
+        let mut ctx = Context::new();
+        let r1 = ctx.add_rule("A", b"a {B:a}");
+        let _ = ctx.add_rule("B", b"b {C:a}");
+        let _ = ctx.add_rule("C", b"c");
+        ctx.initialize(101);
+        let random_size = ctx.get_random_len_for_ruleid(&r1);
+        let tree = ctx.generate_tree_from_rule(r1, random_size);
+
+        let src_dir = "/tmp/nautilus_chunkstore_save_src";
+        let _ = fs::remove_dir_all(src_dir);
+        fs::create_dir_all(format!("{src_dir}/outputs/chunks")).expect("RAND_save_load_src");
+        let mut cks = ChunkStore::new(src_dir.to_string());
+        cks.add_tree(tree, &ctx);
+        let chunk_count = cks.chunk_names.len();
+        cks.save().expect("RAND_save_load_save");
+
+        let reloaded = ChunkStore::load(src_dir).expect("RAND_save_load_load");
+        assert_eq!(reloaded.work_dir, src_dir);
+        assert_eq!(reloaded.chunk_names.len(), chunk_count);
+        assert_eq!(reloaded.trees(), cks.trees());
+        assert_eq!(
+            reloaded.nts_to_chunks[&ctx.nt_id("A")].len(),
+            cks.nts_to_chunks[&ctx.nt_id("A")].len()
+        );
+
+        // A state file copied to a different directory must have its reloaded store read and
+        // write chunks under the new directory, not the one that was in effect at save() time.
+        let dst_dir = "/tmp/nautilus_chunkstore_save_dst";
+        fs::create_dir_all(format!("{dst_dir}/outputs/chunks")).expect("RAND_save_load_dst");
+        fs::copy(
+            format!("{src_dir}/chunkstore.state"),
+            format!("{dst_dir}/chunkstore.state"),
+        )
+        .expect("RAND_save_load_copy");
+        let moved = ChunkStore::load(dst_dir).expect("RAND_save_load_load_moved");
+        assert_eq!(moved.work_dir, dst_dir);
+    }
+}
+
+// Throughput benchmarks for the chunk-store hot paths. These are plain `#[test]`s rather than
+// `#[bench]` because the crate targets stable Rust; each one is a no-op unless `RUN_SLOW_TESTS`
+// is set in the environment, so `cargo test` stays fast by default. Run with:
+//   RUN_SLOW_TESTS=1 cargo test --release -- --nocapture bench_
+#[cfg(test)]
+mod benches {
+    use chunkstore::ChunkStore;
+    use context::Context;
+    use newtypes::RuleID;
+    use std::fs;
+    use std::time::Instant;
+    use tree::{Tree, TreeLike};
+
+    fn slow_tests_enabled() -> bool {
+        std::env::var("RUN_SLOW_TESTS").is_ok()
+    }
+
+    // Times `f` once over `item_count` units of work and reports ns/iter and units/sec, in the
+    // same spirit as `cargo bench`'s output.
+    fn bench<F: FnOnce()>(label: &str, item_count: usize, f: F) {
+        let start = Instant::now();
+        f();
+        let elapsed = start.elapsed();
+        let ns_per_iter = elapsed.as_nanos() as f64 / item_count.max(1) as f64;
+        let per_sec = item_count as f64 / elapsed.as_secs_f64();
+        println!("{label}: {ns_per_iter:.1} ns/iter, {per_sec:.1} items/sec ({item_count} items in {elapsed:?})");
+    }
+
+    // A representative grammar with enough recursion and alternatives that `generate_tree_from_rule`
+    // produces trees with a realistic mix of shared and novel subtrees to dedup against.
+    fn build_context() -> (Context, RuleID) {
+        let mut ctx = Context::new();
+        let r1 = ctx.add_rule("A", b"a {B:a} {B:a}");
+        let _ = ctx.add_rule("B", b"b {C:a}");
+        let _ = ctx.add_rule("B", b"b {A:a}");
+        let _ = ctx.add_rule("C", b"c {B:a}");
+        let _ = ctx.add_rule("C", b"c");
+        ctx.initialize(101);
+        (ctx, r1)
+    }
+
+    fn build_trees(ctx: &Context, r: RuleID, num_trees: usize) -> Vec<Tree> {
+        (0..num_trees)
+            .map(|_| {
+                let len = ctx.get_random_len_for_ruleid(&r);
+                ctx.generate_tree_from_rule(r, len)
+            })
+            .collect()
+    }
+
+    // Wipes any chunk files a previous run left behind before re-scanning the directory, so
+    // back-to-back runs always start from a cold store instead of having every chunk
+    // pre-marked as seen (and its insertion silently skipped) by `ChunkStore::new`.
+    fn fresh_store(work_dir: &str) -> ChunkStore {
+        let _ = fs::remove_dir_all(work_dir);
+        fs::create_dir_all(format!("{work_dir}/outputs/chunks")).expect("RAND_bench_setup");
+        ChunkStore::new(work_dir.to_string())
+    }
+
+    #[test]
+    fn bench_add_tree() {
+        if !slow_tests_enabled() {
+            return;
+        }
+        let (ctx, r1) = build_context();
+        let trees = build_trees(&ctx, r1, 2000);
+        let mut store = fresh_store("/tmp/nautilus_bench_add_tree");
+        let num_trees = trees.len();
+        bench("add_tree", num_trees, move || {
+            for tree in trees {
+                store.add_tree(tree, &ctx);
+            }
+        });
+    }
+
+    #[test]
+    fn bench_get_alternative_to() {
+        if !slow_tests_enabled() {
+            return;
+        }
+        let (ctx, r1) = build_context();
+        let mut store = fresh_store("/tmp/nautilus_bench_get_alternative_to");
+        for tree in build_trees(&ctx, r1, 2000) {
+            store.add_tree(tree, &ctx);
+        }
+        let lookups = 100_000;
+        bench("get_alternative_to (warm store)", lookups, || {
+            for _ in 0..lookups {
+                let _ = store.get_alternative_to(r1, &ctx);
+            }
+        });
+    }
+
+    #[test]
+    fn bench_get_chunk() {
+        if !slow_tests_enabled() {
+            return;
+        }
+        let (ctx, r1) = build_context();
+        let mut store = fresh_store("/tmp/nautilus_bench_get_chunk");
+        for tree in build_trees(&ctx, r1, 2000) {
+            store.add_tree(tree, &ctx);
+        }
+        let reads = 10_000;
+        bench("get_chunk (disk read)", reads, || {
+            for _ in 0..reads {
+                let _ = store.get_chunk();
+            }
+        });
+    }
 }